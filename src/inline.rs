@@ -0,0 +1,265 @@
+//! A fixed-capacity, allocation-free sibling of [`StrStack`](crate::StrStack).
+
+use core::fmt;
+use core::ops::Index;
+use core::str;
+
+use crate::{Iter, Storage, Writer};
+
+/// A fixed-capacity string arena with no heap allocation.
+///
+/// `InlineStrStack` stores up to `BYTES` bytes of string data and up to `STRINGS` strings inline,
+/// making it usable in `static` declarations and interrupt/embedded contexts where the heap is
+/// unavailable or undesirable. Its API mirrors [`StrStack`](crate::StrStack), but insertion is
+/// fallible by default: [`try_push`](InlineStrStack::try_push) returns `None` instead of growing
+/// when either budget would be exceeded, while [`push`](InlineStrStack::push) panics.
+pub struct InlineStrStack<const BYTES: usize, const STRINGS: usize> {
+    data: [u8; BYTES],
+    bytes: usize,
+    ends: [usize; STRINGS],
+    count: usize,
+}
+
+impl<const BYTES: usize, const STRINGS: usize> Storage for InlineStrStack<BYTES, STRINGS> {
+    #[inline]
+    fn full(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(self.data.get_unchecked(..self.bytes)) }
+    }
+
+    #[inline]
+    fn ends(&self) -> &[usize] {
+        unsafe { self.ends.get_unchecked(..self.count) }
+    }
+
+    #[inline]
+    fn push_bytes(&mut self, s: &str) -> bool {
+        if s.len() > self.remaining_bytes() {
+            return false;
+        }
+        self.data[self.bytes..self.bytes + s.len()].copy_from_slice(s.as_bytes());
+        self.bytes += s.len();
+        true
+    }
+
+    #[inline]
+    fn push_end(&mut self) -> bool {
+        if self.count >= STRINGS {
+            return false;
+        }
+        self.ends[self.count] = self.bytes;
+        self.count += 1;
+        true
+    }
+}
+
+impl<const BYTES: usize, const STRINGS: usize> InlineStrStack<BYTES, STRINGS> {
+    /// Create a new, empty `InlineStrStack`.
+    #[inline]
+    pub const fn new() -> Self {
+        InlineStrStack {
+            data: [0; BYTES],
+            bytes: 0,
+            ends: [0; STRINGS],
+            count: 0,
+        }
+    }
+
+    /// Push a string onto the stack, without panicking when either budget would be exceeded.
+    ///
+    /// Returns the index of the new string, or `None` if `s` doesn't fit in the remaining byte
+    /// or string-count budget.
+    #[inline]
+    pub fn try_push(&mut self, s: &str) -> Option<usize> {
+        if self.count >= STRINGS || !self.push_bytes(s) {
+            return None;
+        }
+        self.push_end();
+        Some(self.count - 1)
+    }
+
+    /// Push a string onto the stack.
+    ///
+    /// This returns the index of the string on the stack.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `s` would exceed the byte or string-count budget.
+    #[inline]
+    pub fn push(&mut self, s: &str) -> usize {
+        self.try_push(s).expect("InlineStrStack capacity exceeded")
+    }
+
+    /// Returns the number of bytes that can still be pushed before the byte budget is exhausted.
+    #[inline]
+    pub fn remaining_bytes(&self) -> usize {
+        BYTES - self.bytes
+    }
+
+    /// Returns the number of additional strings that can be pushed before the string-count
+    /// budget is exhausted.
+    #[inline]
+    pub fn remaining_strings(&self) -> usize {
+        STRINGS - self.count
+    }
+
+    /// Iterate over the strings on the stack.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, Self> {
+        Iter {
+            storage: self,
+            front: 0,
+            back: self.count,
+        }
+    }
+
+    /// Remove the top string from the stack.
+    ///
+    /// Returns true iff a string was removed.
+    #[inline]
+    pub fn pop(&mut self) -> bool {
+        if self.count == 0 {
+            false
+        } else {
+            self.count -= 1;
+            self.bytes = if self.count == 0 { 0 } else { self.ends[self.count - 1] };
+            true
+        }
+    }
+
+    /// Clear the stack.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.bytes = 0;
+    }
+
+    /// Returns the number of strings on the stack.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Returns true iff the stack contains no strings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns a writer helper for this string stack.
+    ///
+    /// This is useful for building a string in-place on the string-stack.
+    ///
+    /// # Panics
+    ///
+    /// Obtaining a writer never fails, but finishing one does: dropping or
+    /// [`finish`](Writer::finish)ing a `Writer` pushes the new string's end marker, which panics
+    /// if the stack is already at its `STRINGS` budget. Check
+    /// [`remaining_strings`](InlineStrStack::remaining_strings) before writing if that budget
+    /// might already be exhausted.
+    #[inline]
+    pub fn writer(&mut self) -> Writer<'_, Self> {
+        Writer(self)
+    }
+
+    /// # Safety
+    ///
+    /// `index` must be less than `self.len()`.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, index: usize) -> &str {
+        Storage::get_unchecked(self, index)
+    }
+}
+
+impl<const BYTES: usize, const STRINGS: usize> Default for InlineStrStack<BYTES, STRINGS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const BYTES: usize, const STRINGS: usize> Index<usize> for InlineStrStack<BYTES, STRINGS> {
+    type Output = str;
+    #[inline]
+    fn index(&self, index: usize) -> &str {
+        unsafe {
+            assert!(index < self.len(), "index out of bounds");
+            self.get_unchecked(index)
+        }
+    }
+}
+
+impl<const BYTES: usize, const STRINGS: usize> fmt::Debug for InlineStrStack<BYTES, STRINGS> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, const BYTES: usize, const STRINGS: usize> IntoIterator for &'a InlineStrStack<BYTES, STRINGS> {
+    type IntoIter = Iter<'a, InlineStrStack<BYTES, STRINGS>>;
+    type Item = &'a str;
+    #[inline]
+    fn into_iter(self) -> Iter<'a, InlineStrStack<BYTES, STRINGS>> {
+        self.iter()
+    }
+}
+
+#[test]
+fn test_inline_basic() {
+    let mut stack: InlineStrStack<16, 4> = InlineStrStack::new();
+    let first = stack.push("one");
+    let second = stack.push("two");
+    let third = stack.push("three");
+
+    assert_eq!(&stack[first], "one");
+    assert_eq!(&stack[second], "two");
+    assert_eq!(&stack[third], "three");
+
+    assert_eq!(stack.len(), 3);
+
+    assert!(stack.pop());
+    assert_eq!(stack.len(), 2);
+
+    assert!(stack.pop());
+    assert!(stack.pop());
+    assert_eq!(stack.len(), 0);
+    assert!(!stack.pop());
+}
+
+#[test]
+fn test_inline_try_push_overflow() {
+    let mut stack: InlineStrStack<4, 4> = InlineStrStack::new();
+    assert_eq!(stack.try_push("abcd"), Some(0));
+    assert_eq!(stack.try_push("e"), None);
+
+    let mut stack: InlineStrStack<16, 1> = InlineStrStack::new();
+    assert_eq!(stack.try_push("one"), Some(0));
+    assert_eq!(stack.try_push("two"), None);
+}
+
+#[test]
+fn test_inline_writer() {
+    use core::fmt::Write;
+
+    let mut stack: InlineStrStack<32, 4> = InlineStrStack::new();
+    let first = {
+        let mut w = stack.writer();
+        write!(w, "first ").unwrap();
+        write!(w, "second").unwrap();
+        w.finish()
+    };
+    assert_eq!(&stack[first], "first second");
+}
+
+#[test]
+fn test_inline_iter() {
+    let mut stack: InlineStrStack<16, 4> = InlineStrStack::new();
+    stack.push("one");
+    stack.push("two");
+    stack.push("three");
+
+    let v1: Vec<_> = stack.iter().collect();
+    let v2: Vec<_> = stack.iter().rev().collect();
+
+    assert_eq!(&v1[..], &["one", "two", "three"]);
+    assert_eq!(&v2[..], &["three", "two", "one"]);
+}
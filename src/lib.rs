@@ -16,11 +16,159 @@
 //! assert_eq!(&stack[third], "three");
 //! ```
 //!
-use std::ops::Index;
-use std::fmt::{self, Write};
-use std::io::{self, Read};
-use std::iter::FromIterator;
-use std::slice;
+//! ## `no_std`
+//!
+//! This crate is `no_std` when built without the default `std` feature, backed by `alloc`
+//! instead. The `consume`/`consume_lossy` methods are still available in that mode, built on top
+//! of the crate-local [`io::Read`] trait instead of `std::io::Read`; `consume_split` and
+//! `consume_lines` are `std`-only, as they rely on `std::io::BufRead`.
+//!
+//! For contexts where even the heap is unavailable, [`InlineStrStack`] is a fixed-capacity
+//! sibling that stores its data and strings in inline arrays instead.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+use std::collections::TryReserveError;
+#[cfg(not(feature = "std"))]
+use alloc::collections::TryReserveError;
+#[cfg(feature = "std")]
+use std::string::String;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use core::fmt::{self, Write};
+use core::iter::FromIterator;
+use core::ops::Index;
+#[cfg(feature = "std")]
+use core::ops::Range;
+use core::str;
+
+/// A minimal IO abstraction, shared by the `std` and `no_std` builds.
+///
+/// When the `std` feature is enabled, [`Error`] and [`Result`] are the familiar `std::io` types,
+/// and [`Read`] is a crate-local trait implemented for every `std::io::Read` type (`&[u8]`,
+/// `File`, ...), so those work here for free. When built
+/// without `std`, this module provides a crate-local substitute with the same names, satisfiable
+/// by `core_io` or any other minimal reader.
+pub mod io {
+    #[cfg(feature = "std")]
+    mod imp {
+        pub use std::io::Error;
+        pub use std::io::Result;
+
+        /// A source of bytes to read from.
+        pub trait Read {
+            /// Pull some bytes from this source into `buf`, returning the number read.
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+        }
+
+        impl<R: std::io::Read> Read for R {
+            #[inline]
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+                std::io::Read::read(self, buf)
+            }
+        }
+
+        pub(crate) fn invalid_data() -> Error {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    mod imp {
+        /// The error produced by a failed read, when built without the `std` feature.
+        #[derive(Debug)]
+        pub struct Error;
+
+        pub type Result<T> = core::result::Result<T, Error>;
+
+        /// A source of bytes to read from.
+        pub trait Read {
+            /// Pull some bytes from this source into `buf`, returning the number read.
+            fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+        }
+
+        pub(crate) fn invalid_data() -> Error {
+            Error
+        }
+    }
+
+    pub use self::imp::{Error, Read, Result};
+    pub(crate) use self::imp::invalid_data;
+}
+
+mod inline;
+pub use inline::InlineStrStack;
+
+/// A backing store for an arena of strings: a byte buffer plus the end offset of each string
+/// pushed into it. Shared by the heap-allocated `StrStack` and the fixed-capacity
+/// `InlineStrStack` so that `Iter`, `Index` and `Writer` only need to be implemented once.
+trait Storage {
+    /// The valid, already-written prefix of the backing buffer.
+    fn full(&self) -> &str;
+
+    /// The end offset (into `full()`) of each string pushed so far, in order. The start offset
+    /// of string `i` is `ends()[i - 1]`, or `0` for `i == 0`.
+    fn ends(&self) -> &[usize];
+
+    /// Append `s` to the backing buffer. Returns `false` if it doesn't fit.
+    fn push_bytes(&mut self, s: &str) -> bool;
+
+    /// Register a new end marker at the current length. Returns `false` if the string-count
+    /// budget is exhausted.
+    fn push_end(&mut self) -> bool;
+
+    #[inline]
+    fn start_of(&self, index: usize) -> usize {
+        if index == 0 {
+            0
+        } else {
+            unsafe { *self.ends().get_unchecked(index - 1) }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `index` must be less than the number of strings pushed so far.
+    #[inline]
+    unsafe fn get_unchecked(&self, index: usize) -> &str {
+        let start = self.start_of(index);
+        let end = *self.ends().get_unchecked(index);
+        self.full().get_unchecked(start..end)
+    }
+}
+
+impl Storage for StrStack {
+    #[inline]
+    fn full(&self) -> &str {
+        &self.data
+    }
+
+    #[inline]
+    fn ends(&self) -> &[usize] {
+        // Skip the leading sentinel; see `with_capacity`.
+        unsafe { self.ends.get_unchecked(1..) }
+    }
+
+    #[inline]
+    fn push_bytes(&mut self, s: &str) -> bool {
+        self.data.push_str(s);
+        true
+    }
+
+    #[inline]
+    fn push_end(&mut self) -> bool {
+        self.ends.push(self.data.len());
+        true
+    }
+}
 
 #[derive(Clone, Default)]
 pub struct StrStack {
@@ -39,10 +187,22 @@ impl Index<usize> for StrStack {
     }
 }
 
-#[derive(Clone)]
-pub struct Iter<'a> {
-    data: &'a str,
-    ends: &'a [usize],
+/// An iterator over the strings in a `StrStack` or `InlineStrStack`.
+///
+/// `Storage` is a private implementation detail shared between the two backing stores; it is
+/// intentionally not exported, so `#[allow(private_bounds)]` silences the resulting lint.
+#[allow(private_bounds)]
+pub struct Iter<'a, S: Storage> {
+    storage: &'a S,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, S: Storage> Clone for Iter<'a, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Iter { storage: self.storage, front: self.front, back: self.back }
+    }
 }
 
 impl fmt::Debug for StrStack {
@@ -51,20 +211,16 @@ impl fmt::Debug for StrStack {
     }
 }
 
-impl<'a> Iterator for Iter<'a> {
+impl<'a, S: Storage> Iterator for Iter<'a, S> {
     type Item = &'a str;
     #[inline]
     fn next(&mut self) -> Option<&'a str> {
-        unsafe {
-            let len = self.ends.len();
-            if len == 1 {
-                None
-            } else {
-                let start = *self.ends.get_unchecked(0);
-                let end = *self.ends.get_unchecked(1);
-                self.ends = slice::from_raw_parts(self.ends.as_ptr().offset(1), len - 1);
-                Some(self.data.slice_unchecked(start, end))
-            }
+        if self.front >= self.back {
+            None
+        } else {
+            let s = unsafe { self.storage.get_unchecked(self.front) };
+            self.front += 1;
+            Some(s)
         }
     }
 
@@ -78,35 +234,30 @@ impl<'a> Iterator for Iter<'a> {
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = self.ends.len() - 1;
+        let len = self.back - self.front;
         (len, Some(len))
     }
 }
 
-impl<'a> ExactSizeIterator for Iter<'a> {}
+impl<'a, S: Storage> ExactSizeIterator for Iter<'a, S> {}
 
-impl<'a> DoubleEndedIterator for Iter<'a> {
+impl<'a, S: Storage> DoubleEndedIterator for Iter<'a, S> {
     #[inline]
     fn next_back(&mut self) -> Option<&'a str> {
-        unsafe {
-            let len = self.ends.len();
-            if len == 1 {
-                None
-            } else {
-                let start = *self.ends.get_unchecked(len-2);
-                let end = *self.ends.get_unchecked(len-1);
-                self.ends = slice::from_raw_parts(self.ends.as_ptr(), len - 1);
-                Some(self.data.slice_unchecked(start, end))
-            }
+        if self.front >= self.back {
+            None
+        } else {
+            self.back -= 1;
+            Some(unsafe { self.storage.get_unchecked(self.back) })
         }
     }
 }
 
 impl<'a> IntoIterator for &'a StrStack {
-    type IntoIter = Iter<'a>;
+    type IntoIter = Iter<'a, StrStack>;
     type Item = &'a str;
     #[inline]
-    fn into_iter(self) -> Iter<'a> {
+    fn into_iter(self) -> Iter<'a, StrStack> {
         self.iter()
     }
 }
@@ -133,6 +284,28 @@ impl StrStack {
         stack
     }
 
+    /// Create a new StrStack with the given capacity, without aborting on allocation failure.
+    ///
+    /// This is the fallible counterpart to `with_capacity`.
+    #[inline]
+    pub fn try_with_capacity(bytes: usize, strings: usize) -> Result<StrStack, TryReserveError> {
+        let mut data = String::new();
+        data.try_reserve(bytes)?;
+        let mut ends = Vec::new();
+        ends.try_reserve(strings + 1)?;
+        ends.push(0);
+        Ok(StrStack { data, ends })
+    }
+
+    /// Reserve capacity for at least `bytes` more bytes and `strings` more strings, without
+    /// aborting on allocation failure.
+    #[inline]
+    pub fn try_reserve(&mut self, bytes: usize, strings: usize) -> Result<(), TryReserveError> {
+        self.data.try_reserve(bytes)?;
+        self.ends.try_reserve(strings)?;
+        Ok(())
+    }
+
     /// Push a string onto the string stack.
     ///
     /// This returns the index of the string on the stack.
@@ -143,12 +316,44 @@ impl StrStack {
         self.len() - 1
     }
 
+    /// Push a string onto the string stack, without aborting on allocation failure.
+    ///
+    /// On success, this returns the index of the string on the stack. On failure, the stack is
+    /// left completely unchanged.
+    #[inline]
+    pub fn try_push(&mut self, s: &str) -> Result<usize, TryReserveError> {
+        self.data.try_reserve(s.len())?;
+        self.ends.try_reserve(1)?;
+        self.data.push_str(s);
+        self.ends.push(self.data.len());
+        Ok(self.len() - 1)
+    }
+
+    /// Extend the stack from an iterator, without aborting on allocation failure.
+    ///
+    /// This is the fallible counterpart to `extend`. Strings pushed before a failing reservation
+    /// remain on the stack; the failing string itself is not added.
+    pub fn try_extend<S, T>(&mut self, iterator: T) -> Result<(), TryReserveError>
+    where
+        S: AsRef<str>,
+        T: IntoIterator<Item = S>,
+    {
+        let iterator = iterator.into_iter();
+        let (min, _) = iterator.size_hint();
+        self.ends.try_reserve(min)?;
+        for v in iterator {
+            self.try_push(v.as_ref())?;
+        }
+        Ok(())
+    }
+
     /// Iterate over the strings on the stack.
     #[inline]
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_, StrStack> {
         Iter {
-            data: &self.data,
-            ends: &self.ends,
+            storage: self,
+            front: 0,
+            back: self.len(),
         }
     }
 
@@ -179,6 +384,12 @@ impl StrStack {
         self.ends.len() - 1
     }
 
+    /// Returns true iff the stack contains no strings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Truncate the stack to `len` strings.
     #[inline]
     pub fn truncate(&mut self, len: usize) {
@@ -190,13 +401,130 @@ impl StrStack {
     ///
     /// Returns the index of the new string or an IO Error.
     pub fn consume<R: io::Read>(&mut self, mut source: R) -> io::Result<usize> {
-        match source.read_to_string(&mut self.data) {
-            Ok(_) => {
-                self.ends.push(self.data.len());
-                Ok(self.len() - 1)
-            },
-            Err(e) => Err(e),
+        let start = self.data.len();
+        let mut buf = [0u8; 4096];
+        let mut carry: Vec<u8> = Vec::new();
+        loop {
+            let n = match source.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) => {
+                    self.data.truncate(start);
+                    return Err(e);
+                }
+            };
+            if n == 0 {
+                break;
+            }
+            carry.extend_from_slice(&buf[..n]);
+            match str::from_utf8(&carry) {
+                Ok(s) => {
+                    self.data.push_str(s);
+                    carry.clear();
+                }
+                Err(e) => {
+                    let valid_up_to = e.valid_up_to();
+                    self.data.push_str(unsafe { str::from_utf8_unchecked(&carry[..valid_up_to]) });
+                    if e.error_len().is_some() {
+                        self.data.truncate(start);
+                        return Err(io::invalid_data());
+                    }
+                    carry.drain(..valid_up_to);
+                }
+            }
+        }
+        if !carry.is_empty() {
+            self.data.truncate(start);
+            return Err(io::invalid_data());
+        }
+        self.ends.push(self.data.len());
+        Ok(self.len() - 1)
+    }
+
+    /// Read from `source`, splitting on each occurrence of `delim` and pushing each segment
+    /// (with the delimiter stripped) as its own string.
+    ///
+    /// Returns the range of indices that were created.
+    #[cfg(feature = "std")]
+    pub fn consume_split<R: std::io::BufRead>(&mut self, mut source: R, delim: u8) -> io::Result<Range<usize>> {
+        let start = self.len();
+        let mut tmp = Vec::new();
+        loop {
+            tmp.clear();
+            let n = source.read_until(delim, &mut tmp)?;
+            if n == 0 {
+                break;
+            }
+            if tmp.last() == Some(&delim) {
+                tmp.pop();
+            }
+            let s = str::from_utf8(&tmp).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            self.push(s);
+        }
+        Ok(start..self.len())
+    }
+
+    /// Read lines from `source`, pushing each line (without its trailing `\n`) as its own string.
+    ///
+    /// This is a convenience wrapper around `consume_split` with `delim = b'\n'`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn consume_lines<R: std::io::BufRead>(&mut self, source: R) -> io::Result<Range<usize>> {
+        self.consume_split(source, b'\n')
+    }
+
+    /// Read from `source`, replacing any invalid UTF-8 byte sequences with the replacement
+    /// character (`U+FFFD`) instead of failing.
+    ///
+    /// Returns the index of the new string.
+    pub fn consume_lossy<R: io::Read>(&mut self, mut source: R) -> io::Result<usize> {
+        let start = self.data.len();
+        let mut raw = [0u8; 4096];
+        let mut carry: Vec<u8> = Vec::new();
+        loop {
+            let n = match source.read(&mut raw) {
+                Ok(n) => n,
+                Err(e) => {
+                    self.data.truncate(start);
+                    return Err(e);
+                }
+            };
+            if n == 0 {
+                break;
+            }
+            carry.extend_from_slice(&raw[..n]);
+            let mut rest: &[u8] = &carry;
+            loop {
+                match str::from_utf8(rest) {
+                    Ok(s) => {
+                        self.data.push_str(s);
+                        rest = &[];
+                        break;
+                    }
+                    Err(e) => {
+                        let valid_up_to = e.valid_up_to();
+                        self.data.push_str(unsafe { str::from_utf8_unchecked(&rest[..valid_up_to]) });
+                        match e.error_len() {
+                            Some(len) => {
+                                self.data.push('\u{FFFD}');
+                                rest = &rest[valid_up_to + len..];
+                            }
+                            None => {
+                                // The trailing sequence is incomplete; it may be finished by the
+                                // next read, so carry it over instead of replacing it now.
+                                rest = &rest[valid_up_to..];
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            carry = rest.to_vec();
+        }
+        if !carry.is_empty() {
+            self.data.push('\u{FFFD}');
         }
+        self.ends.push(self.data.len());
+        Ok(self.len() - 1)
     }
 
     /// Returns a writer helper for this string stack.
@@ -221,7 +549,7 @@ impl StrStack {
     /// assert_eq!(&s[index], "Hello World!");
     /// ```
     #[inline]
-    pub fn writer(&mut self) -> Writer {
+    pub fn writer(&mut self) -> Writer<'_, StrStack> {
         Writer(self)
     }
 
@@ -244,11 +572,12 @@ impl StrStack {
         writer.finish()
     }
 
+    /// # Safety
+    ///
+    /// `index` must be less than `self.len()`.
     #[inline]
     pub unsafe fn get_unchecked(&self, index: usize) -> &str {
-        let start = *self.ends.get_unchecked(index);
-        let end = *self.ends.get_unchecked(index+1);
-        self.data.slice_unchecked(start, end)
+        Storage::get_unchecked(self, index)
     }
 }
 
@@ -271,33 +600,56 @@ impl<S> FromIterator<S> for StrStack where S: AsRef<str> {
     }
 }
 
-pub struct Writer<'a>(&'a mut StrStack);
+/// A helper for building a string in-place on a `StrStack` or `InlineStrStack`.
+///
+/// `Storage` is a private implementation detail shared between the two backing stores; it is
+/// intentionally not exported, so `#[allow(private_bounds)]` silences the resulting lint.
+#[allow(private_bounds)]
+pub struct Writer<'a, S: Storage>(&'a mut S);
 
-impl<'a> Writer<'a> {
+#[allow(private_bounds)]
+impl<'a, S: Storage> Writer<'a, S> {
     /// Finish pushing the string onto the stack and return its index.
     #[inline]
     pub fn finish(self) -> usize {
-        // We push on drop.
-        self.0.len()
+        // We push the end marker on drop.
+        self.0.ends().len()
     }
 }
 
-impl<'a> fmt::Write for Writer<'a> {
+impl<'a> Writer<'a, StrStack> {
+    /// Write a string into the in-progress string, without aborting on allocation failure.
+    ///
+    /// This also reserves room for the end marker that `finish`/`Drop` will push, so that
+    /// finishing the string afterwards can't abort either.
     #[inline]
-    fn write_str(&mut self, s: &str) -> fmt::Result {
+    pub fn try_write_str(&mut self, s: &str) -> Result<(), TryReserveError> {
+        self.0.data.try_reserve(s.len())?;
+        self.0.ends.try_reserve(1)?;
         self.0.data.push_str(s);
         Ok(())
     }
+}
+
+impl<'a, S: Storage> fmt::Write for Writer<'a, S> {
+    #[inline]
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.push_bytes(s) {
+            Ok(())
+        } else {
+            Err(fmt::Error)
+        }
+    }
     #[inline]
     fn write_char(&mut self, c: char) -> fmt::Result {
-        self.0.data.push(c);
-        Ok(())
+        let mut buf = [0u8; 4];
+        self.write_str(c.encode_utf8(&mut buf))
     }
 }
 
-impl<'a> Drop for Writer<'a> {
+impl<'a, S: Storage> Drop for Writer<'a, S> {
     fn drop(&mut self) {
-        self.0.ends.push(self.0.data.len());
+        assert!(self.0.push_end(), "capacity exceeded");
     }
 }
 
@@ -328,6 +680,55 @@ fn test_basic() {
     assert!(!stack.pop());
 }
 
+#[test]
+fn test_try_push() {
+    let mut stack = StrStack::new();
+    let first = stack.try_push("one").unwrap();
+    let second = stack.try_push("two").unwrap();
+    assert_eq!(&stack[first], "one");
+    assert_eq!(&stack[second], "two");
+    assert_eq!(stack.len(), 2);
+}
+
+#[test]
+fn test_try_with_capacity() {
+    let mut stack = StrStack::try_with_capacity(16, 2).unwrap();
+    let first = stack.try_push("one").unwrap();
+    let second = stack.try_push("two").unwrap();
+    assert_eq!(&stack[first], "one");
+    assert_eq!(&stack[second], "two");
+}
+
+#[test]
+fn test_try_reserve() {
+    let mut stack = StrStack::new();
+    stack.try_reserve(16, 2).unwrap();
+    let first = stack.try_push("one").unwrap();
+    assert_eq!(&stack[first], "one");
+}
+
+#[test]
+fn test_try_extend() {
+    let mut stack = StrStack::new();
+    stack.try_extend(["one", "two", "three"]).unwrap();
+    assert_eq!(stack.len(), 3);
+    assert_eq!(&stack[0], "one");
+    assert_eq!(&stack[1], "two");
+    assert_eq!(&stack[2], "three");
+}
+
+#[test]
+fn test_try_write_str() {
+    let mut stack = StrStack::new();
+    let idx = {
+        let mut w = stack.writer();
+        w.try_write_str("first ").unwrap();
+        w.try_write_str("second").unwrap();
+        w.finish()
+    };
+    assert_eq!(&stack[idx], "first second");
+}
+
 #[test]
 fn test_consume() {
     let mut stack = StrStack::new();
@@ -335,20 +736,48 @@ fn test_consume() {
     assert_eq!(&stack[idx], "testing");
 }
 
+#[test]
+fn test_consume_invalid_utf8_leaves_stack_unchanged() {
+    let mut stack = StrStack::new();
+    stack.push("before");
+    assert!(stack.consume(&b"abc\xff"[..]).is_err());
+    let idx = stack.push("after");
+    assert_eq!(&stack[idx], "after");
+    assert_eq!(stack.len(), 2);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_consume_lines() {
+    let mut stack = StrStack::new();
+    let range = stack.consume_lines("one\ntwo\nthree".as_bytes()).unwrap();
+    assert_eq!(range, 0..3);
+    assert_eq!(&stack[0], "one");
+    assert_eq!(&stack[1], "two");
+    assert_eq!(&stack[2], "three");
+}
+
+#[test]
+fn test_consume_lossy() {
+    let mut stack = StrStack::new();
+    let idx = stack.consume_lossy(&b"hello \xffworld"[..]).unwrap();
+    assert_eq!(&stack[idx], "hello \u{FFFD}world");
+}
+
 #[test]
 fn test_writer() {
     let mut stack = StrStack::new();
     let first = {
         let mut w = stack.writer();
-        write!(w, "{}", "first ").unwrap();
-        write!(w, "{}", "second").unwrap();
+        write!(w, "first ").unwrap();
+        write!(w, "second").unwrap();
         w.finish()
     };
 
     let second = {
         let mut w = stack.writer();
-        write!(w, "{}", "third ").unwrap();
-        write!(w, "{}", "fourth").unwrap();
+        write!(w, "third ").unwrap();
+        write!(w, "fourth").unwrap();
         w.finish()
     };
     assert_eq!(&stack[first], "first second");